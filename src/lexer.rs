@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt::Display, iter::Peekable, str::Chars};
+use std::{borrow::Cow, collections::HashMap, fmt::Display, iter::Peekable, str::CharIndices};
 
 use ordered_float::OrderedFloat;
 use phf::phf_map;
@@ -10,7 +10,7 @@ use SingleCharacterToken::*;
 
 /// The location of a [Token]"s lexeme in the
 /// source code.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Location {
     /// Our vertical location in the file
     line_number: u16,
@@ -51,9 +51,41 @@ impl Location {
 pub enum LexerError {
     #[error("Unexpected `{character}` at {location}")]
     UnexpectedCharacter { character: char, location: Location },
+
+    #[error("Unterminated string starting at {location}")]
+    UnterminatedString { location: Location },
+
+    #[error("Malformed number literal `{lexeme}` at {location}")]
+    MalformedNumber { lexeme: String, location: Location },
+
+    #[error("Number literal `{lexeme}` at {location} is out of range for a 32-bit float")]
+    NumberOutOfRange { lexeme: String, location: Location },
+
+    #[error("Invalid escape sequence `{sequence}` at {location}")]
+    InvalidEscape { sequence: String, location: Location },
+
+    #[error("Invalid character literal at {location}")]
+    InvalidCharLiteral { location: Location },
+}
+
+/// The half-open range of [Location]`s a lexeme spans, from its first
+/// character up to (but not including) the one right after its last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
 }
 
-static LEXEME_TO_TOKEN_MAPPER: phf::Map<&'static str, Token> = phf_map! {
+/// A `node` paired with the [Span] of source it was lexed/parsed from, so
+/// downstream diagnostics can always point back at where something came
+/// from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+static LEXEME_TO_TOKEN_MAPPER: phf::Map<&'static str, Token<'static>> = phf_map! {
     // Map Keywords
     "and" => Token::KeywordToken(And),
     "class" => Token::KeywordToken(Class),
@@ -95,54 +127,57 @@ static LEXEME_TO_TOKEN_MAPPER: phf::Map<&'static str, Token> = phf_map! {
 };
 
 lazy_static! {
-    static ref TOKEN_TO_LEXEME_MAPPER: HashMap<Token, &'static str> = {
-        let mut mapper = HashMap::with_capacity(48);
-
-        // Map Keywords
-        mapper.insert(Token::KeywordToken(And), "and");
-        mapper.insert(Token::KeywordToken(Class), "class");
-        mapper.insert(Token::KeywordToken(If), "if");
-        mapper.insert(Token::KeywordToken(Else), "else");
-        mapper.insert(Token::KeywordToken(True), "true");
-        mapper.insert(Token::KeywordToken(False), "false");
-        mapper.insert(Token::KeywordToken(Fun), "fun");
-        mapper.insert(Token::KeywordToken(For), "for");
-        mapper.insert(Token::KeywordToken(While), "while");
-        mapper.insert(Token::KeywordToken(Var), "var");
-        mapper.insert(Token::KeywordToken(Nil), "nil");
-        mapper.insert(Token::KeywordToken(Or), "or");
-        mapper.insert(Token::KeywordToken(Print), "print");
-        mapper.insert(Token::KeywordToken(Return), "return");
-        mapper.insert(Token::KeywordToken(Super), "super");
-        mapper.insert(Token::KeywordToken(This), "this");
-
-        // Map Single and Double character tokens
-        mapper.insert(Token::Single(LeftBrace), "(");
-        mapper.insert(Token::Single(RightBrace), ")");
-        mapper.insert(Token::Single(LeftParenthesis), "{");
-        mapper.insert(Token::Single(RightParenthesis), "}");
-        mapper.insert(Token::Single(Plus), "+");
-        mapper.insert(Token::Single(Minus), "-");
-        mapper.insert(Token::Single(Comma), ",");
-        mapper.insert(Token::Single(Dot), ".");
-        mapper.insert(Token::Single(SemiColon), ";");
-        mapper.insert(Token::Single(Star), "*");
-        mapper.insert(Token::Single(Not), "!");
-        mapper.insert(Token::Single(Slash), "/");
-        mapper.insert(Token::Double(NotEqual), "!=");
-        mapper.insert(Token::Single(EqualSign), "=");
-        mapper.insert(Token::Double(EqualEqualSign), "==");
-        mapper.insert(Token::Single(LessThan), "<");
-        mapper.insert(Token::Double(LessThanOrEqual), "<=");
-        mapper.insert(Token::Single(GreaterThan), ">");
-        mapper.insert(Token::Double(GreaterThanOrEqual), ">=");
-
+    static ref KEYWORD_TO_LEXEME_MAPPER: HashMap<Keyword, &'static str> = {
+        let mut mapper = HashMap::with_capacity(16);
+        mapper.insert(And, "and");
+        mapper.insert(Class, "class");
+        mapper.insert(If, "if");
+        mapper.insert(Else, "else");
+        mapper.insert(True, "true");
+        mapper.insert(False, "false");
+        mapper.insert(Fun, "fun");
+        mapper.insert(For, "for");
+        mapper.insert(While, "while");
+        mapper.insert(Var, "var");
+        mapper.insert(Nil, "nil");
+        mapper.insert(Or, "or");
+        mapper.insert(Print, "print");
+        mapper.insert(Return, "return");
+        mapper.insert(Super, "super");
+        mapper.insert(This, "this");
+        mapper
+    };
+    static ref SINGLE_TO_LEXEME_MAPPER: HashMap<SingleCharacterToken, &'static str> = {
+        let mut mapper = HashMap::with_capacity(14);
+        mapper.insert(LeftBrace, "(");
+        mapper.insert(RightBrace, ")");
+        mapper.insert(LeftParenthesis, "{");
+        mapper.insert(RightParenthesis, "}");
+        mapper.insert(Plus, "+");
+        mapper.insert(Minus, "-");
+        mapper.insert(Comma, ",");
+        mapper.insert(Dot, ".");
+        mapper.insert(SemiColon, ";");
+        mapper.insert(Star, "*");
+        mapper.insert(Not, "!");
+        mapper.insert(Slash, "/");
+        mapper.insert(EqualSign, "=");
+        mapper.insert(LessThan, "<");
+        mapper.insert(GreaterThan, ">");
+        mapper
+    };
+    static ref DOUBLE_TO_LEXEME_MAPPER: HashMap<DoubleCharacterToken, &'static str> = {
+        let mut mapper = HashMap::with_capacity(4);
+        mapper.insert(NotEqual, "!=");
+        mapper.insert(EqualEqualSign, "==");
+        mapper.insert(LessThanOrEqual, "<=");
+        mapper.insert(GreaterThanOrEqual, ">=");
         mapper
     };
 }
 
 /// All
-#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum SingleCharacterToken {
     /// ## (
     LeftParenthesis,
@@ -176,7 +211,7 @@ pub enum SingleCharacterToken {
     LessThan,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum DoubleCharacterToken {
     /// ## !=
     NotEqual,
@@ -188,20 +223,42 @@ pub enum DoubleCharacterToken {
     LessThanOrEqual,
 }
 
-/// Literals can be numbers, variable names, function names, class names, or strings
-/// surrounded by double quotes `"`
+/// Literals can be numbers, variable names, function names, class names, strings
+/// surrounded by double quotes `"`, or a single character surrounded by `'`.
+///
+/// `Identifier` and `StringLiteral` both borrow their text straight out of
+/// the source buffer when they can, via `Cow::Borrowed`, and only own it
+/// when something forces the issue: a string with an escape sequence
+/// decodes to text that no longer matches the source bytes, and a token
+/// that has to outlive its source (see [Literal::to_owned_literal]) can't
+/// keep borrowing from it either way.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Literal {
+pub enum Literal<'src> {
     Number(OrderedFloat<f32>),
     /// An identifier can be a variable name, a function name ...
-    Identifier(String),
-    /// A string is anything within double quotes `"<string>"`
-    StringLiteral(String),
+    Identifier(Cow<'src, str>),
+    /// A string is anything within double quotes `"<string>"`, with
+    /// `\n`, `\t`, `\r`, `\\`, `\"` and `\u{XXXX}` escapes decoded.
+    StringLiteral(Cow<'src, str>),
+    /// A single character surrounded by `'`, e.g. `'a'`, `'\n'`, `'\u{41}'`
+    Char(char),
+}
+
+impl Literal<'_> {
+    /// Copy the borrowed text (if any) into an owned, `'static` literal.
+    fn to_owned_literal(&self) -> Literal<'static> {
+        match self {
+            Literal::Number(value) => Literal::Number(*value),
+            Literal::Identifier(value) => Literal::Identifier(Cow::Owned(value.clone().into_owned())),
+            Literal::StringLiteral(value) => Literal::StringLiteral(Cow::Owned(value.clone().into_owned())),
+            Literal::Char(value) => Literal::Char(*value),
+        }
+    }
 }
 
 /// Keywords are literals that have been reserved for
 /// the language"s internal use
-#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Keyword {
     /// ## and
     And,
@@ -237,17 +294,38 @@ pub enum Keyword {
     This,
 }
 
-/// All the valid tokens in the `lox` language
+/// All the valid tokens in the `lox` language.
+///
+/// Tokens are generic over the lifetime of the source they were scanned
+/// from so that `Identifier`/`StringLiteral` can borrow their lexeme
+/// instead of allocating. Callers that need a token to outlive the
+/// source, e.g. to cache it, can go through [Token::to_owned].
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Token {
+pub enum Token<'src> {
     Eof,
-    LiteralToken(Literal),
+    LiteralToken(Literal<'src>),
     KeywordToken(Keyword),
     Single(SingleCharacterToken),
     Double(DoubleCharacterToken),
 }
 
-impl Display for Token {
+impl<'src> Token<'src> {
+    /// Copy any borrowed lexeme out of the source buffer, producing a
+    /// token that is no longer tied to `'src`. Only needed by callers
+    /// that must hold on to a token past the lifetime of the source,
+    /// e.g. a parser error that is reported well after lexing finished.
+    pub fn to_owned(&self) -> Token<'static> {
+        match self {
+            Self::Eof => Token::Eof,
+            Self::LiteralToken(literal) => Token::LiteralToken(literal.to_owned_literal()),
+            Self::KeywordToken(keyword) => Token::KeywordToken(*keyword),
+            Self::Single(single) => Token::Single(*single),
+            Self::Double(double) => Token::Double(*double),
+        }
+    }
+}
+
+impl Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Eof => f.write_str("END_OF_FILE"),
@@ -255,122 +333,104 @@ impl Display for Token {
                 Literal::Number(value) => f.write_str(&value.to_string()),
                 Literal::Identifier(value) => f.write_str(value),
                 Literal::StringLiteral(value) => f.write_str(value),
+                Literal::Char(value) => f.write_str(&value.to_string()),
             },
-            _ => f.write_str(TOKEN_TO_LEXEME_MAPPER.get(self).unwrap()),
+            Self::KeywordToken(keyword) => f.write_str(KEYWORD_TO_LEXEME_MAPPER.get(keyword).unwrap()),
+            Self::Single(single) => f.write_str(SINGLE_TO_LEXEME_MAPPER.get(single).unwrap()),
+            Self::Double(double) => f.write_str(DOUBLE_TO_LEXEME_MAPPER.get(double).unwrap()),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct TokenStream(Vec<Token>);
+/// A lazily produced stream of [Token]`s. A parser pulls one token at a
+/// time from this instead of forcing the whole source to be scanned up
+/// front, which is both cheaper on early-exit/error paths and a more
+/// natural fit for a recursive-descent parser.
+pub type TokenStream<'src> = Peekable<TokenIterator<'src>>;
 
+/// Scans `source` one [Token] at a time. Whitespace and comments are
+/// skipped internally, so every item this iterator yields is either a
+/// real token or the [LexerError] that prevented one from being formed.
+///
+/// Once [Token::Eof] has been yielded, every subsequent call to `next`
+/// returns `None`.
 #[derive(Debug)]
-pub struct Lexer {
-    source_code: String,
+pub struct TokenIterator<'src> {
+    source: &'src str,
+    chars: Peekable<CharIndices<'src>>,
     current_location: Location,
+    exhausted: bool,
 }
 
-impl Lexer {
-    /// Create a new lexer for the provided source code
-    pub fn new(source_code: String) -> Self {
-        Lexer {
-            source_code,
+impl<'src> TokenIterator<'src> {
+    fn new(source: &'src str) -> Self {
+        TokenIterator {
+            source,
+            chars: source.char_indices().peekable(),
             current_location: Location::default(),
+            exhausted: false,
         }
     }
 
-    /// Scan the source code to generate a stream of [Token]`s producing
-    /// [LexerError] if any errors are encountered.
-    pub fn lex(&mut self) -> Result<TokenStream, Vec<LexerError>> {
-        let mut errors = Vec::new();
-        let mut tokens = Vec::with_capacity(self.source_code.len());
-        let mut code = self.source_code.chars().peekable();
-        while let Some(character) = code.next() {
-            match character {
-                ' ' | '\r' | '\t' => {}
-                '\n' => self.current_location.advance_row(),
-                '(' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get("(").cloned().unwrap()),
-                ')' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get(")").cloned().unwrap()),
-                '{' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get("{").cloned().unwrap()),
-                '}' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get("}").cloned().unwrap()),
-                '+' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get("+").cloned().unwrap()),
-                '-' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get("-").cloned().unwrap()),
-                ',' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get(",").cloned().unwrap()),
-                '.' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get(".").cloned().unwrap()),
-                ';' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get(";").cloned().unwrap()),
-                '*' => tokens.push(LEXEME_TO_TOKEN_MAPPER.get("*").cloned().unwrap()),
-                '!' => Self::add_double_or_single_token(&mut tokens, character, &mut code),
-                '=' => Self::add_double_or_single_token(&mut tokens, character, &mut code),
-                '<' => Self::add_double_or_single_token(&mut tokens, character, &mut code),
-                '>' => Self::add_double_or_single_token(&mut tokens, character, &mut code),
-                '/' => Self::consume_comment(&mut tokens, character, &mut self.current_location, &mut code),
-                '"' => Self::add_string_literal(&mut tokens, &mut self.current_location, &mut code),
-                '0'..='9' => {
-                    Self::add_number_literal(&mut tokens, character, &mut self.current_location, &mut code)
-                }
-                'A'..='Z' | 'a'..='z' | '_' => Self::add_identifier_or_keyword(
-                    &mut tokens,
-                    character,
-                    &mut self.current_location,
-                    &mut code,
-                ),
-                _ => errors.push(LexerError::UnexpectedCharacter {
-                    character,
-                    location: self.current_location.clone(),
-                }),
-            }
-            self.current_location.advance_col();
-        }
-        tokens.push(Token::Eof);
-        if errors.is_empty() {
-            Ok(TokenStream(tokens))
-        } else {
-            Err(errors)
-        }
+    /// The byte offset of whatever character `self.chars` would yield next,
+    /// or the end of the source if there isn't one.
+    fn next_offset(&mut self) -> usize {
+        self.chars.peek().map(|&(offset, _)| offset).unwrap_or(self.source.len())
+    }
+
+    /// The source slice starting at byte offset `start` and running up to
+    /// (but not including) whatever character `self.chars` would yield next.
+    fn slice_from(&mut self, start: usize) -> &'src str {
+        let end = self.next_offset();
+        &self.source[start..end]
     }
 
     /// Look ahead one step. Add a [Token::Double] if the next character matched the expected
     /// character. Otherwise add a [Token::Single]
-    fn add_double_or_single_token(tokens: &mut Vec<Token>, current_character: char, code: &mut Peekable<Chars>) {
+    fn add_double_or_single_token(&mut self, current_character: char) -> Token<'src> {
         let expected_next_character = '=';
-        if Self::one_step_look_ahead(expected_next_character, code) {
+        if Self::one_step_look_ahead(expected_next_character, &mut self.chars) {
+            self.current_location.advance_col();
             // TODO: Advance column by 1
             let double_lexeme = format!("{}{}", current_character, expected_next_character);
-            tokens.push(LEXEME_TO_TOKEN_MAPPER.get(&double_lexeme).cloned().unwrap());
+            LEXEME_TO_TOKEN_MAPPER.get(&double_lexeme).cloned().unwrap()
         } else {
-            let single_token = LEXEME_TO_TOKEN_MAPPER.get(&current_character.to_string());
-            tokens.push(single_token.cloned().unwrap())
+            LEXEME_TO_TOKEN_MAPPER
+                .get(&current_character.to_string())
+                .cloned()
+                .unwrap()
         }
     }
 
     /// Look ahead one character and if the next character is another '/`,
     /// consume the rest of the line. If not, add a single '/' token to the list
     /// to the list
-    fn consume_comment(
-        tokens: &mut Vec<Token>,
-        current_character: char,
-        current_location: &mut Location,
-        code: &mut Peekable<Chars>,
-    ) {
+    fn consume_comment(&mut self, current_character: char) -> Option<Token<'src>> {
         let expected_next_char = '/';
-        if Self::one_step_look_ahead(expected_next_char, code) {
-            let mut advanced_iter = code.skip_while(|&character| character != '\n');
+        if Self::one_step_look_ahead(expected_next_char, &mut self.chars) {
+            let current_location = &mut self.current_location;
+            let mut advanced_iter = (&mut self.chars).skip_while(|&(_, character)| character != '\n');
             match advanced_iter.next() {
                 None => {}
-                Some('\n') => current_location.advance_row(),
+                Some((_, '\n')) => current_location.advance_row(),
                 Some(_) => panic!("we should never hit this arm"),
             }
+            None
         } else {
-            let single_token = LEXEME_TO_TOKEN_MAPPER.get(&current_character.to_string());
-            tokens.push(single_token.cloned().unwrap())
+            Some(
+                LEXEME_TO_TOKEN_MAPPER
+                    .get(&current_character.to_string())
+                    .cloned()
+                    .unwrap(),
+            )
         }
     }
 
     /// Peek at the next character. If it is what we `expect`, we consume it by
     /// advancing the iterator then return `true`. Otherwise, we return false
-    fn one_step_look_ahead(expect: char, code_characters: &mut Peekable<Chars>) -> bool {
-        if let Some(next_character) = code_characters.peek() {
-            match expect.cmp(next_character) {
+    fn one_step_look_ahead(expect: char, code_characters: &mut Peekable<CharIndices>) -> bool {
+        if let Some(&(_, next_character)) = code_characters.peek() {
+            match expect.cmp(&next_character) {
                 std::cmp::Ordering::Equal => {
                     code_characters.next();
                     return true;
@@ -385,124 +445,877 @@ impl Lexer {
     /// Called when we encounter a `"`. we scan forward looking for
     /// a closing `"`. If we find one, we recognize the lexeme between
     /// the first `"` and the last  `"` we encountered as a string token.
+    /// If the string contains no
+    /// `\` escapes the token borrows straight from the source; the
+    /// moment one is seen we fall back to building an owned `String` with
+    /// the escape decoded, since the result no longer matches the source
+    /// bytes.
     ///
     /// If a closing `"` is not found, that is we reach the end of the file before
-    /// encountering another `"`, we record that as an error.
-    fn add_string_literal(
-        tokens: &mut Vec<Token>,
-        current_location: &mut Location,
-        code: &mut Peekable<Chars>,
-    ) {
-        let mut maybe_string = String::new();
-        for character in code {
-            if character == '"' {
-                // We found the closing quotes of this string
-                current_location.advance_col();
-                tokens.push(Token::LiteralToken(Literal::StringLiteral(maybe_string)));
-                return;
-            } else {
-                // We treat anything between the quotations as part of the string
-                if character == '\n' {
-                    current_location.advance_row();
-                } else {
-                    current_location.advance_col();
+    /// encountering another `"`, we record a [LexerError::UnterminatedString] at
+    /// `start_location` instead of panicking; if an invalid escape was
+    /// already seen in the string, that error is returned in its place and
+    /// no [LexerError::UnterminatedString] is reported. There is nothing left to
+    /// resynchronize on in that case since we've already consumed to the
+    /// end of the source.
+    fn add_string_literal(&mut self, start: usize, start_location: Location) -> Result<Token<'src>, LexerError> {
+        // Account for the opening `"` itself, which the caller already
+        // consumed from `self.chars` before calling us. We take over
+        // `current_location` accounting completely from here so that a
+        // multi-line string ends up with the correct column on whatever
+        // row its closing `"` lands on.
+        self.current_location.advance_col();
+        let mut decoded: Option<String> = None;
+        // The first escape error we see, if any. We keep scanning to the
+        // closing `"` regardless so that a bad `\` doesn't leave it
+        // unconsumed to open a bogus second string later on the line.
+        let mut error: Option<LexerError> = None;
+        loop {
+            match self.chars.next() {
+                Some((offset, '"')) => {
+                    self.current_location.advance_col();
+                    if let Some(error) = error {
+                        return Err(error);
+                    }
+                    let literal = match decoded {
+                        Some(owned) => Cow::Owned(owned),
+                        None => Cow::Borrowed(&self.source[start..offset]),
+                    };
+                    return Ok(Token::LiteralToken(Literal::StringLiteral(literal)));
+                }
+                Some((offset, '\\')) => {
+                    let escape_location = self.current_location.clone();
+                    self.current_location.advance_col();
+                    let buffer = decoded.get_or_insert_with(|| self.source[start..offset].to_owned());
+                    match self.decode_escape(&escape_location) {
+                        Ok(decoded_char) => buffer.push(decoded_char),
+                        Err(decode_error) => {
+                            error.get_or_insert(decode_error);
+                        }
+                    }
+                }
+                Some((_, '\n')) => {
+                    self.current_location.advance_row();
+                    if let Some(buffer) = decoded.as_mut() {
+                        buffer.push('\n');
+                    }
+                }
+                Some((_, character)) => {
+                    self.current_location.advance_col();
+                    if let Some(buffer) = decoded.as_mut() {
+                        buffer.push(character);
+                    }
+                }
+                None => return Err(error.unwrap_or(LexerError::UnterminatedString { location: start_location })),
+            }
+        }
+    }
+
+    /// Called right after a `\` inside a string or character literal.
+    /// Decodes `\n`, `\t`, `\r`, `\\`, `\"`, `\'` and `\u{XXXX}`, recording
+    /// a [LexerError::InvalidEscape] for anything else.
+    fn decode_escape(&mut self, escape_location: &Location) -> Result<char, LexerError> {
+        match self.chars.next() {
+            Some((_, 'n')) => {
+                self.current_location.advance_col();
+                Ok('\n')
+            }
+            Some((_, 't')) => {
+                self.current_location.advance_col();
+                Ok('\t')
+            }
+            Some((_, 'r')) => {
+                self.current_location.advance_col();
+                Ok('\r')
+            }
+            Some((_, '\\')) => {
+                self.current_location.advance_col();
+                Ok('\\')
+            }
+            Some((_, '"')) => {
+                self.current_location.advance_col();
+                Ok('"')
+            }
+            Some((_, '\'')) => {
+                self.current_location.advance_col();
+                Ok('\'')
+            }
+            Some((_, 'u')) => {
+                self.current_location.advance_col();
+                self.decode_unicode_escape(escape_location)
+            }
+            Some((_, other)) => {
+                self.current_location.advance_col();
+                Err(LexerError::InvalidEscape {
+                    sequence: format!("\\{}", other),
+                    location: escape_location.clone(),
+                })
+            }
+            None => Err(LexerError::InvalidEscape {
+                sequence: "\\".to_owned(),
+                location: escape_location.clone(),
+            }),
+        }
+    }
+
+    /// Called right after the `u` of a `\u{XXXX}` escape. Expects a brace
+    /// delimited run of hex digits naming a Unicode scalar value.
+    fn decode_unicode_escape(&mut self, escape_location: &Location) -> Result<char, LexerError> {
+        if !matches!(self.chars.peek(), Some((_, '{'))) {
+            return Err(LexerError::InvalidEscape {
+                sequence: "\\u".to_owned(),
+                location: escape_location.clone(),
+            });
+        }
+        self.chars.next();
+        self.current_location.advance_col();
+
+        let mut hex_digits = String::new();
+        loop {
+            match self.chars.peek() {
+                Some(&(_, '}')) => {
+                    self.chars.next();
+                    self.current_location.advance_col();
+                    break;
+                }
+                Some(&(_, character)) if character.is_ascii_hexdigit() => {
+                    hex_digits.push(character);
+                    self.chars.next();
+                    self.current_location.advance_col();
+                }
+                _ => {
+                    return Err(LexerError::InvalidEscape {
+                        sequence: format!("\\u{{{}", hex_digits),
+                        location: escape_location.clone(),
+                    })
+                }
+            }
+        }
+
+        let invalid = || LexerError::InvalidEscape {
+            sequence: format!("\\u{{{}}}", hex_digits),
+            location: escape_location.clone(),
+        };
+        let code_point = u32::from_str_radix(&hex_digits, 16).map_err(|_| invalid())?;
+        char::from_u32(code_point).ok_or_else(invalid)
+    }
+
+    /// Called when we encounter a `'`. Reads exactly one (possibly
+    /// escaped) character followed by a closing `'`, as in `'a'`, `'\n'`
+    /// or `'\u{41}'`. A bad escape is a [LexerError::InvalidEscape]; anything
+    /// else is a [LexerError::InvalidCharLiteral].
+    fn add_char_literal(&mut self, start_location: Location) -> Result<Token<'src>, LexerError> {
+        // Account for the opening `'`, which the caller already consumed
+        // from `self.chars` before calling us.
+        self.current_location.advance_col();
+        let decoded_char = match self.chars.next() {
+            Some((_, '\\')) => {
+                self.current_location.advance_col();
+                match self.decode_escape(&start_location) {
+                    Ok(character) => character,
+                    Err(error) => {
+                        // The escape failed, possibly without consuming
+                        // through its own closing `'`. Resync onto it now
+                        // so a later iteration doesn't mistake it for the
+                        // opening quote of a bogus second char literal.
+                        self.resync_to_closing_quote();
+                        return Err(error);
+                    }
+                }
+            }
+            Some((_, character)) if character != '\'' => {
+                self.current_location.advance_col();
+                character
+            }
+            _ => return Err(LexerError::InvalidCharLiteral { location: start_location }),
+        };
+        match self.chars.next() {
+            Some((_, '\'')) => {
+                self.current_location.advance_col();
+                Ok(Token::LiteralToken(Literal::Char(decoded_char)))
+            }
+            _ => Err(LexerError::InvalidCharLiteral { location: start_location }),
+        }
+    }
+
+    /// Consumes up to and including the next `'`, stopping early at a
+    /// newline or EOF. Used to resynchronize after a char literal's escape
+    /// fails partway through, so the real closing quote gets consumed
+    /// instead of being left to open a bogus second literal.
+    fn resync_to_closing_quote(&mut self) {
+        loop {
+            match self.chars.peek() {
+                Some(&(_, '\'')) => {
+                    self.chars.next();
+                    self.current_location.advance_col();
+                    return;
+                }
+                Some(&(_, '\n')) | None => return,
+                Some(_) => {
+                    self.chars.next();
+                    self.current_location.advance_col();
                 }
-                maybe_string.push(character);
             }
         }
-        // If we consumed until the end but found not closing `"` we
-        // emit an error.
-        // TODO: Change the signature to take the list of errors
     }
 
     /// Called whenever we encounter a char digit.
     ///
     /// Consumes characters until we encounter a character that is neither
-    /// a digit nor a `.` (decimal point)
-    fn add_number_literal(
-        tokens: &mut Vec<Token>,
-        first_digit: char,
-        current_location: &mut Location,
-        code: &mut Peekable<Chars>,
-    ) {
-        let mut maybe_number = String::from(first_digit);
-        while let Some(&character) = code.peek() {
+    /// a digit nor a `.` (decimal point). If the resulting lexeme doesn't
+    /// parse as an `f32` (e.g. `1.2.3`) we record a
+    /// [LexerError::MalformedNumber]; if it parses but overflows to
+    /// infinity we record a [LexerError::NumberOutOfRange]. Either way we
+    /// keep scanning from right after the lexeme instead of panicking.
+    fn add_number_literal(&mut self, start: usize, start_location: Location) -> Result<Token<'src>, LexerError> {
+        // Account for the first digit, which the caller already consumed
+        // from `self.chars` before calling us.
+        self.current_location.advance_col();
+        while let Some(&(_, character)) = self.chars.peek() {
             // Notice that unlike in the book, we allow users to write `123.`.
             // This will be interpreted as 123.0
             if character.is_ascii_digit() || character == '.' {
-                maybe_number.push(character);
-                code.next();
-                current_location.advance_col();
+                self.chars.next();
+                self.current_location.advance_col();
             } else {
-                // We've reached the end of the digit. We store a number token
-                // TODO: What if the attempt to parse the number fails?
-                // TODO: We should probably guard against numbers larger than f32::MAX
-                let maybe_number_float: f32 = maybe_number.parse().unwrap();
-                tokens.push(Token::LiteralToken(Literal::Number(OrderedFloat(maybe_number_float))));
-                return;
+                break;
             }
         }
+        let lexeme = self.slice_from(start);
+        match lexeme.parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(Token::LiteralToken(Literal::Number(OrderedFloat(value)))),
+            Ok(_) => Err(LexerError::NumberOutOfRange {
+                lexeme: lexeme.to_owned(),
+                location: start_location,
+            }),
+            Err(_) => Err(LexerError::MalformedNumber {
+                lexeme: lexeme.to_owned(),
+                location: start_location,
+            }),
+        }
     }
 
     /// Called whenever we encounter a character that is neither an operator
     /// nor part of a string literal. We interpret such as either parts
     /// of keywords or as variable identifiers.
-    fn add_identifier_or_keyword(
-        tokens: &mut Vec<Token>,
-        first_character: char,
-        current_location: &mut Location,
-        code: &mut Peekable<Chars>,
-    ) {
-        let mut identifier_or_keyword = String::from(first_character);
-        while let Some(&character) = code.peek() {
+    fn add_identifier_or_keyword(&mut self, start: usize) -> Token<'src> {
+        while let Some(&(_, character)) = self.chars.peek() {
             match character {
                 'A'..='Z' | 'a'..='z' | '_' | '0'..='9' => {
-                    identifier_or_keyword.push(character);
-                    code.next();
-                    current_location.advance_col();
+                    self.chars.next();
+                    self.current_location.advance_col();
+                }
+                _ => break,
+            }
+        }
+        let lexeme = self.slice_from(start);
+        match LEXEME_TO_TOKEN_MAPPER.get(lexeme) {
+            Some(keyword) => keyword.clone(),
+            None => Token::LiteralToken(Literal::Identifier(Cow::Borrowed(lexeme))),
+        }
+    }
+}
+
+impl<'src> Iterator for TokenIterator<'src> {
+    type Item = Result<Spanned<Token<'src>>, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+        loop {
+            let start_location = self.current_location.clone();
+            let (offset, character) = match self.chars.next() {
+                Some(next) => next,
+                None => {
+                    self.exhausted = true;
+                    return Some(Ok(Spanned {
+                        node: Token::Eof,
+                        span: Span {
+                            end: start_location.clone(),
+                            start: start_location,
+                        },
+                    }));
                 }
+            };
+            let token = match character {
+                ' ' | '\r' | '\t' => {
+                    self.current_location.advance_col();
+                    continue;
+                }
+                '\n' => {
+                    self.current_location.advance_row();
+                    continue;
+                }
+                '(' => LEXEME_TO_TOKEN_MAPPER.get("(").cloned().unwrap(),
+                ')' => LEXEME_TO_TOKEN_MAPPER.get(")").cloned().unwrap(),
+                '{' => LEXEME_TO_TOKEN_MAPPER.get("{").cloned().unwrap(),
+                '}' => LEXEME_TO_TOKEN_MAPPER.get("}").cloned().unwrap(),
+                '+' => LEXEME_TO_TOKEN_MAPPER.get("+").cloned().unwrap(),
+                '-' => LEXEME_TO_TOKEN_MAPPER.get("-").cloned().unwrap(),
+                ',' => LEXEME_TO_TOKEN_MAPPER.get(",").cloned().unwrap(),
+                '.' => LEXEME_TO_TOKEN_MAPPER.get(".").cloned().unwrap(),
+                ';' => LEXEME_TO_TOKEN_MAPPER.get(";").cloned().unwrap(),
+                '*' => LEXEME_TO_TOKEN_MAPPER.get("*").cloned().unwrap(),
+                '!' => self.add_double_or_single_token(character),
+                '=' => self.add_double_or_single_token(character),
+                '<' => self.add_double_or_single_token(character),
+                '>' => self.add_double_or_single_token(character),
+                '/' => match self.consume_comment(character) {
+                    Some(token) => token,
+                    None => continue,
+                },
+                // The string helper can cross a newline mid-scan (a
+                // string literal is allowed to span multiple lines), so
+                // these three helpers own their start-to-end `current_location`
+                // accounting outright, including the opening character,
+                // rather than leaving it to a trailing `advance_col()`
+                // here that has no idea which row it's catching up on.
+                '"' => {
+                    return Some(self.add_string_literal(offset + 1, start_location.clone()).map(
+                        |token| Spanned {
+                            node: token,
+                            span: Span {
+                                start: start_location,
+                                end: self.current_location.clone(),
+                            },
+                        },
+                    ))
+                }
+                '0'..='9' => {
+                    return Some(self.add_number_literal(offset, start_location.clone()).map(
+                        |token| Spanned {
+                            node: token,
+                            span: Span {
+                                start: start_location,
+                                end: self.current_location.clone(),
+                            },
+                        },
+                    ))
+                }
+                '\'' => {
+                    return Some(self.add_char_literal(start_location.clone()).map(|token| Spanned {
+                        node: token,
+                        span: Span {
+                            start: start_location,
+                            end: self.current_location.clone(),
+                        },
+                    }))
+                }
+                'A'..='Z' | 'a'..='z' | '_' => self.add_identifier_or_keyword(offset),
                 _ => {
-                    // We've reached teh end of the keyword or identifier
-                    match LEXEME_TO_TOKEN_MAPPER.get(&identifier_or_keyword) {
-                        Some(keyword) => tokens.push(keyword.clone()),
-                        None => tokens.push(Token::LiteralToken(Literal::Identifier(identifier_or_keyword))),
-                    }
-                    return;
+                    let error = LexerError::UnexpectedCharacter {
+                        character,
+                        location: self.current_location.clone(),
+                    };
+                    self.current_location.advance_col();
+                    return Some(Err(error));
+                }
+            };
+            self.current_location.advance_col();
+            return Some(Ok(Spanned {
+                node: token,
+                span: Span {
+                    start: start_location,
+                    end: self.current_location.clone(),
+                },
+            }));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Lexer {
+    source_code: String,
+}
+
+impl Lexer {
+    /// Create a new lexer for the provided source code
+    pub fn new(source_code: String) -> Self {
+        Lexer { source_code }
+    }
+
+    /// Pull a lazy [TokenStream] over this lexer's source. Tokens are only
+    /// produced as the parser asks for them, and borrow their lexemes
+    /// straight out of `self.source_code`.
+    pub fn token_stream(&self) -> TokenStream<'_> {
+        TokenIterator::new(&self.source_code).peekable()
+    }
+
+    /// Scan the source code to generate a stream of [Token]`s producing
+    /// [LexerError] if any errors are encountered.
+    ///
+    /// This is a thin `collect()` over [Lexer::token_stream] kept around for
+    /// callers that still want the whole source scanned eagerly.
+    pub fn lex(&self) -> Result<Vec<Spanned<Token<'_>>>, Vec<LexerError>> {
+        let mut tokens = Vec::with_capacity(self.source_code.len());
+        let mut errors = Vec::new();
+        for result in TokenIterator::new(&self.source_code) {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Incremental, editor-oriented re-lexing, for a future language server.
+/// Gated behind the `lsp` feature so that consumers who just want to lex a
+/// whole file once don't pay for a `ropey` dependency.
+#[cfg(feature = "lsp")]
+pub mod incremental {
+    use std::ops::Range;
+
+    use ropey::Rope;
+
+    use super::{Lexer, LexerError, Location, Span, Spanned, Token, TokenIterator};
+
+    /// A [Lexer] backed by a [Rope] instead of a flat `String`, so small
+    /// edits only re-scan the lines they actually touched instead of
+    /// the whole buffer.
+    pub struct IncrementalLexer {
+        rope: Rope,
+        /// The last full token scan. Tokens are owned (see
+        /// [Token::to_owned]) since they have to outlive any one
+        /// `String` snapshot we take of the rope to re-lex a slice.
+        tokens: Vec<Spanned<Token<'static>>>,
+    }
+
+    impl IncrementalLexer {
+        pub fn new(source: String) -> Self {
+            let rope = Rope::from_str(&source);
+            // A fresh buffer has no prior lex errors to reconcile with,
+            // so an error-free scan is the common case; fall back to an
+            // empty token list rather than refusing to construct.
+            let tokens = Lexer::new(source)
+                .lex()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|spanned| Spanned {
+                    node: spanned.node.to_owned(),
+                    span: spanned.span,
+                })
+                .collect();
+            IncrementalLexer { rope, tokens }
+        }
+
+        /// Replace the byte range `edit` with `new_text` and re-tokenize
+        /// only the lines it touched, returning the updated token list
+        /// alongside any [LexerError]s the rescanned region produced.
+        ///
+        /// We widen the edit to whole lines before re-scanning, then keep
+        /// widening: a string literal is allowed to span multiple lines,
+        /// so a cached token's span can start well before the edited lines or end
+        /// well after them. Selecting the window by line alone, without
+        /// re-checking once it moves, would cut such a token in half and
+        /// leave a stale, overlapping entry behind; we instead grow the
+        /// window to the union of every cached span it already overlaps,
+        /// until a pass changes nothing.
+        pub fn relex(&mut self, edit: Range<usize>, new_text: &str) -> (&[Spanned<Token<'static>>], Vec<LexerError>) {
+            // Cached tokens carry pre-edit line numbers, so the edited
+            // lines are taken from the rope before it changes.
+            let mut start_line = self.rope.byte_to_line(edit.start) as u16;
+            let mut end_line = self.rope.byte_to_line(edit.end) as u16;
+            let old_len_lines = self.rope.len_lines();
+
+            let start_char = self.rope.byte_to_char(edit.start);
+            let end_char = self.rope.byte_to_char(edit.end);
+            self.rope.remove(start_char..end_char);
+            self.rope.insert(start_char, new_text);
+            let line_delta = self.rope.len_lines() as i32 - old_len_lines as i32;
+
+            let (first, last) = loop {
+                let first = self
+                    .tokens
+                    .iter()
+                    .position(|spanned| spanned.span.end.line_number >= start_line)
+                    .unwrap_or(self.tokens.len());
+                let last = self.tokens[first..]
+                    .iter()
+                    .position(|spanned| spanned.span.start.line_number > end_line)
+                    .map(|offset| first + offset)
+                    .unwrap_or(self.tokens.len());
+
+                let widened_start = self.tokens[first..last]
+                    .iter()
+                    .map(|spanned| spanned.span.start.line_number)
+                    .fold(start_line, u16::min);
+                let widened_end = self.tokens[first..last]
+                    .iter()
+                    .map(|spanned| spanned.span.end.line_number)
+                    .fold(end_line, u16::max);
+
+                if widened_start == start_line && widened_end == end_line {
+                    break (first, last);
                 }
+                start_line = widened_start;
+                end_line = widened_end;
+            };
+
+            let rescan_start_byte = self.rope.line_to_byte(start_line as usize);
+            let rescan_end_line = ((end_line as i32 + line_delta) as usize + 1).min(self.rope.len_lines());
+            let rescan_end_byte = self.rope.line_to_byte(rescan_end_line);
+            let rescan_text = self
+                .rope
+                .slice(self.rope.byte_to_char(rescan_start_byte)..self.rope.byte_to_char(rescan_end_byte))
+                .to_string();
+
+            let shift = |location: Location| {
+                Location::from((start_line + location.line_number, location.column_number))
+            };
+            // Whether the rescanned slice runs all the way to the end of the
+            // buffer: when it does, the Eof our rescan produces is the
+            // buffer's real Eof and has to survive the splice below, not
+            // just get dropped along with the rest of the rescan's bookkeeping.
+            let reached_eof = rescan_end_line == self.rope.len_lines();
+            let mut errors = Vec::new();
+            let mut eof_location = None;
+            let mut new_tokens: Vec<Spanned<Token<'static>>> = TokenIterator::new(&rescan_text)
+                .filter_map(|result| match result {
+                    Ok(spanned) => Some(spanned),
+                    Err(error) => {
+                        errors.push(shift_error(error, start_line));
+                        None
+                    }
+                })
+                .take_while(|spanned| match &spanned.node {
+                    Token::Eof => {
+                        eof_location = Some(shift(spanned.span.end));
+                        false
+                    }
+                    _ => true,
+                })
+                .map(|spanned| Spanned {
+                    node: spanned.node.to_owned(),
+                    span: Span {
+                        start: shift(spanned.span.start),
+                        end: shift(spanned.span.end),
+                    },
+                })
+                .collect();
+            if reached_eof {
+                let location = eof_location.unwrap_or_else(|| shift(Location::default()));
+                new_tokens.push(Spanned {
+                    node: Token::Eof,
+                    span: Span { start: location.clone(), end: location },
+                });
             }
+
+            // Every token after the splice window needs its line number
+            // shifted by however many lines the re-lexed region grew or
+            // shrank by.
+            for spanned in &mut self.tokens[last..] {
+                spanned.span.start.line_number = (spanned.span.start.line_number as i32 + line_delta) as u16;
+                spanned.span.end.line_number = (spanned.span.end.line_number as i32 + line_delta) as u16;
+            }
+
+            self.tokens.splice(first..last, new_tokens);
+            (&self.tokens, errors)
+        }
+    }
+
+    /// Shift a [LexerError]'s [Location] down by `start_line`, the same
+    /// way a token [Span] gets shifted in [IncrementalLexer::relex] — the
+    /// rescanned text is a slice starting at `start_line`, so everything
+    /// it reports comes back in slice-relative coordinates.
+    fn shift_error(error: LexerError, start_line: u16) -> LexerError {
+        let shift = |location: Location| Location::from((start_line + location.line_number, location.column_number));
+        match error {
+            LexerError::UnexpectedCharacter { character, location } => LexerError::UnexpectedCharacter {
+                character,
+                location: shift(location),
+            },
+            LexerError::UnterminatedString { location } => LexerError::UnterminatedString { location: shift(location) },
+            LexerError::MalformedNumber { lexeme, location } => LexerError::MalformedNumber {
+                lexeme,
+                location: shift(location),
+            },
+            LexerError::NumberOutOfRange { lexeme, location } => LexerError::NumberOutOfRange {
+                lexeme,
+                location: shift(location),
+            },
+            LexerError::InvalidEscape { sequence, location } => LexerError::InvalidEscape {
+                sequence,
+                location: shift(location),
+            },
+            LexerError::InvalidCharLiteral { location } => LexerError::InvalidCharLiteral { location: shift(location) },
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_one_step_look_ahead() {
-        todo!()
+        let source = "=foo";
+        let mut chars = source.char_indices().peekable();
+        assert!(TokenIterator::one_step_look_ahead('=', &mut chars));
+        assert_eq!(chars.peek(), Some(&(1, 'f')));
+        assert!(!TokenIterator::one_step_look_ahead('x', &mut chars));
     }
 
     #[test]
     fn test_consume_comment() {
-        todo!()
+        let mut single_slash = TokenIterator::new("/=foo");
+        single_slash.chars.next();
+        assert_eq!(
+            single_slash.consume_comment('/'),
+            Some(Token::Single(SingleCharacterToken::Slash))
+        );
+
+        let mut full_comment = TokenIterator::new("//hello\nx");
+        full_comment.chars.next();
+        assert_eq!(full_comment.consume_comment('/'), None);
+        assert_eq!(full_comment.chars.peek(), Some(&(8, 'x')));
     }
 
     #[test]
     fn test_add_double_token() {
-        todo!()
+        let mut double = TokenIterator::new("==");
+        double.chars.next();
+        assert_eq!(
+            double.add_double_or_single_token('='),
+            Token::Double(DoubleCharacterToken::EqualEqualSign)
+        );
+
+        let mut single = TokenIterator::new("= ");
+        single.chars.next();
+        assert_eq!(
+            single.add_double_or_single_token('='),
+            Token::Single(SingleCharacterToken::EqualSign)
+        );
     }
 
     #[test]
     fn test_add_string_literal() {
-        todo!()
+        let mut iter = TokenIterator::new("\"hello\" rest");
+        let start_location = iter.current_location.clone();
+        let (offset, _) = iter.chars.next().unwrap();
+        match iter.add_string_literal(offset + 1, start_location) {
+            Ok(Token::LiteralToken(Literal::StringLiteral(value))) => assert_eq!(value, "hello"),
+            other => panic!("expected a string literal, got {other:?}"),
+        }
     }
 
     #[test]
     fn test_add_number_literal() {
-        todo!()
+        let mut iter = TokenIterator::new("123.45 rest");
+        let start_location = iter.current_location.clone();
+        let (offset, _) = iter.chars.next().unwrap();
+        match iter.add_number_literal(offset, start_location) {
+            Ok(Token::LiteralToken(Literal::Number(value))) => assert_eq!(value.into_inner(), 123.45_f32),
+            other => panic!("expected a number literal, got {other:?}"),
+        }
     }
 
     #[test]
     fn test_add_identifier_or_keyword() {
-        todo!()
+        let mut identifier = TokenIterator::new("foo bar");
+        let (offset, _) = identifier.chars.next().unwrap();
+        assert_eq!(
+            identifier.add_identifier_or_keyword(offset),
+            Token::LiteralToken(Literal::Identifier(Cow::Borrowed("foo")))
+        );
+
+        let mut keyword = TokenIterator::new("while x");
+        let (offset, _) = keyword.chars.next().unwrap();
+        assert_eq!(keyword.add_identifier_or_keyword(offset), Token::KeywordToken(Keyword::While));
+    }
+
+    #[test]
+    fn test_token_iterator_yields_eof_once() {
+        let mut iter = TokenIterator::new("");
+        match iter.next() {
+            Some(Ok(spanned)) => assert_eq!(spanned.node, Token::Eof),
+            other => panic!("expected a single Eof token, got {other:?}"),
+        }
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_token_to_owned() {
+        let lexer = Lexer::new("foo".to_owned());
+        let tokens = lexer.lex().expect("lexing should succeed");
+        assert_eq!(
+            tokens[0].node.to_owned(),
+            Token::LiteralToken(Literal::Identifier(Cow::Borrowed("foo")))
+        );
+    }
+
+    #[test]
+    fn test_multiline_string_span() {
+        let lexer = Lexer::new("\"ab\ncd\"".to_owned());
+        let tokens = lexer.lex().expect("lexing should succeed");
+        let spanned = &tokens[0];
+        match &spanned.node {
+            Token::LiteralToken(Literal::StringLiteral(value)) => assert_eq!(value, "ab\ncd"),
+            other => panic!("expected a string literal, got {other:?}"),
+        }
+        assert_eq!(spanned.span.start, Location::from((0, 0)));
+        assert_eq!(spanned.span.end, Location::from((1, 3)));
+    }
+
+    #[test]
+    fn test_unterminated_string_records_error() {
+        let lexer = Lexer::new("\"abc".to_owned());
+        let errors = lexer.lex().expect_err("an unterminated string should error");
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            LexerError::UnterminatedString { location } => assert_eq!(*location, Location::from((0, 0))),
+            other => panic!("expected UnterminatedString, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_malformed_number_records_error() {
+        let lexer = Lexer::new("1.2.3 x".to_owned());
+        let errors = lexer.lex().expect_err("a malformed number should error");
+        match &errors[0] {
+            LexerError::MalformedNumber { lexeme, location } => {
+                assert_eq!(lexeme, "1.2.3");
+                assert_eq!(*location, Location::from((0, 0)));
+            }
+            other => panic!("expected MalformedNumber, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_number_out_of_range_records_error() {
+        let lexeme = "9".repeat(45);
+        let lexer = Lexer::new(lexeme.clone());
+        let errors = lexer.lex().expect_err("an out-of-range number should error");
+        match &errors[0] {
+            LexerError::NumberOutOfRange { lexeme: reported, .. } => assert_eq!(reported, &lexeme),
+            other => panic!("expected NumberOutOfRange, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_string_escape_decoding() {
+        let lexer = Lexer::new("\"line1\\nline2\\t\\u{41}\"".to_owned());
+        let tokens = lexer.lex().expect("lexing should succeed");
+        match &tokens[0].node {
+            Token::LiteralToken(Literal::StringLiteral(value)) => assert_eq!(value, "line1\nline2\tA"),
+            other => panic!("expected a string literal, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_escape_records_error() {
+        let lexer = Lexer::new("\"bad \\q escape\"".to_owned());
+        let errors = lexer.lex().expect_err("an invalid escape should error");
+        match &errors[0] {
+            LexerError::InvalidEscape { sequence, .. } => assert_eq!(sequence, "\\q"),
+            other => panic!("expected InvalidEscape, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_string_escape_resyncs_to_closing_quote() {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in TokenIterator::new("var y = \"ok \\z bad\" ; print y;") {
+            match result {
+                Ok(token) => tokens.push(token.node),
+                Err(error) => errors.push(error),
+            }
+        }
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            LexerError::InvalidEscape { sequence, .. } => assert_eq!(sequence, "\\z"),
+            other => panic!("expected InvalidEscape, got {other:?}"),
+        }
+        assert!(
+            tokens.iter().any(|token| matches!(token, Token::KeywordToken(Keyword::Print))),
+            "print should still be lexed as its own token, got {tokens:?}"
+        );
+        assert!(
+            tokens.iter().any(|token| matches!(token, Token::LiteralToken(Literal::Identifier(name)) if name == "y")),
+            "y should still be lexed as its own identifier, got {tokens:?}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_char_escape_resyncs_to_closing_quote() {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in TokenIterator::new("'\\u{D800}' print x;") {
+            match result {
+                Ok(token) => tokens.push(token.node),
+                Err(error) => errors.push(error),
+            }
+        }
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], LexerError::InvalidEscape { .. }));
+        assert!(
+            tokens.iter().any(|token| matches!(token, Token::KeywordToken(Keyword::Print))),
+            "print should still be lexed as its own token, got {tokens:?}"
+        );
+    }
+
+    #[test]
+    fn test_char_literal() {
+        let lexer = Lexer::new("'a' '\\n'".to_owned());
+        let tokens = lexer.lex().expect("lexing should succeed");
+        match &tokens[0].node {
+            Token::LiteralToken(Literal::Char(value)) => assert_eq!(*value, 'a'),
+            other => panic!("expected a char literal, got {other:?}"),
+        }
+        match &tokens[1].node {
+            Token::LiteralToken(Literal::Char(value)) => assert_eq!(*value, '\n'),
+            other => panic!("expected a char literal, got {other:?}"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "lsp")]
+    fn test_relex_single_line_edit() {
+        use super::incremental::IncrementalLexer;
+
+        let mut lexer = IncrementalLexer::new("var x = 1;".to_owned());
+        let (tokens, errors) = lexer.relex(4..5, "y");
+        assert!(errors.is_empty());
+        let identifier_name = tokens.iter().find_map(|spanned| match &spanned.node {
+            Token::LiteralToken(Literal::Identifier(name)) => Some(name.clone()),
+            _ => None,
+        });
+        assert_eq!(identifier_name.as_deref(), Some("y"));
+    }
+
+    #[test]
+    #[cfg(feature = "lsp")]
+    fn test_relex_shifts_trailing_spans() {
+        use super::incremental::IncrementalLexer;
+
+        let mut lexer = IncrementalLexer::new("var x = 1;\nprint x;".to_owned());
+        let (tokens, errors) = lexer.relex(11..11, "\n");
+        assert!(errors.is_empty());
+        let print_token = tokens
+            .iter()
+            .find(|spanned| matches!(spanned.node, Token::KeywordToken(Keyword::Print)))
+            .expect("the print token should still be present after the edit");
+        assert_eq!(print_token.span.start.line_number, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "lsp")]
+    fn test_relex_keeps_eof_on_end_of_buffer_edit() {
+        use super::incremental::IncrementalLexer;
+
+        let mut lexer = IncrementalLexer::new("var x = 1;".to_owned());
+        let (tokens, errors) = lexer.relex(8..9, "9");
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens.last().map(|spanned| &spanned.node),
+            Some(&Token::Eof),
+            "relex should not drop the Eof sentinel when the edit window reaches end of buffer, got {tokens:?}"
+        );
     }
 }